@@ -0,0 +1,132 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+    sync::Mutex,
+};
+
+use crate::formats::Entry;
+
+/// Output filename stem for the combined aggregate profile, before any
+/// `.unified`/compression extension is appended.
+pub const AGGREGATE_FILE_STEM: &str = "aggregate";
+
+/// One row of an `--aggregate` coverage summary: a program counter, its
+/// total hit count across every input file, and how many distinct files
+/// touched it.
+#[derive(Debug)]
+pub struct AggregateRow {
+    pub program_counter: usize,
+    pub total_hits: u64,
+    pub files_covering: usize,
+}
+
+impl Display for AggregateRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:x} {} {}",
+            self.program_counter, self.total_hits, self.files_covering
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    total_hits: u64,
+    files_covering: usize,
+}
+
+/// Folds basic-block entries from every trace file into one combined
+/// per-program-counter coverage profile.
+#[derive(Debug, Default)]
+pub struct Aggregator {
+    by_pc: Mutex<HashMap<usize, Stats>>,
+}
+
+impl Aggregator {
+    /// Fold one file's basic-block entries into the running totals.
+    /// Non-BB entries are ignored, since per-block hit aggregation is a
+    /// basic-block coverage concept.
+    pub fn record(&self, entries: &[Entry]) {
+        let mut seen_pcs = HashSet::new();
+        let mut by_pc = self.by_pc.lock().unwrap();
+
+        for entry in entries {
+            let Entry::Bb(entry) = entry else { continue };
+
+            let stats = by_pc.entry(entry.program_counter).or_default();
+            stats.total_hits += entry.hit_counter as u64;
+
+            if seen_pcs.insert(entry.program_counter) {
+                stats.files_covering += 1;
+            }
+        }
+    }
+
+    /// Consume the aggregator, returning its rows sorted by program counter.
+    pub fn into_rows(self) -> Vec<AggregateRow> {
+        let mut rows: Vec<AggregateRow> = self
+            .by_pc
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|(program_counter, stats)| AggregateRow {
+                program_counter,
+                total_hits: stats.total_hits,
+                files_covering: stats.files_covering,
+            })
+            .collect();
+
+        rows.sort_by_key(|row| row.program_counter);
+
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::BasicBlockEntry;
+
+    fn bb(id: usize, program_counter: usize, hit_counter: usize) -> Entry {
+        Entry::Bb(BasicBlockEntry {
+            id,
+            program_counter,
+            hit_counter,
+        })
+    }
+
+    #[test]
+    fn sums_hits_and_dedups_files_covering_per_record_call() {
+        let aggregator = Aggregator::default();
+
+        // One file's entries can repeat the same pc (e.g. a loop body hit
+        // more than once); that should still only count as one file.
+        aggregator.record(&[bb(0, 0x10, 3), bb(1, 0x10, 2), bb(2, 0x20, 5)]);
+        aggregator.record(&[bb(0, 0x10, 1)]);
+
+        let rows = aggregator.into_rows();
+        assert_eq!(rows.len(), 2);
+
+        let pc10 = rows.iter().find(|r| r.program_counter == 0x10).unwrap();
+        assert_eq!(pc10.total_hits, 6);
+        assert_eq!(pc10.files_covering, 2);
+
+        let pc20 = rows.iter().find(|r| r.program_counter == 0x20).unwrap();
+        assert_eq!(pc20.total_hits, 5);
+        assert_eq!(pc20.files_covering, 1);
+    }
+
+    #[test]
+    fn into_rows_is_sorted_by_program_counter() {
+        let aggregator = Aggregator::default();
+        aggregator.record(&[bb(0, 0x30, 1), bb(1, 0x10, 1), bb(2, 0x20, 1)]);
+
+        let pcs: Vec<usize> = aggregator
+            .into_rows()
+            .iter()
+            .map(|row| row.program_counter)
+            .collect();
+        assert_eq!(pcs, vec![0x10, 0x20, 0x30]);
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh3::xxh3_128;
+
+/// Bytes read from the front and back of a file for the cheap partial hash.
+const PARTIAL_HASH_BLOCK: usize = 64 * 1024;
+
+/// What we know about a previously-unified input file.
+///
+/// Hashing a file is the expensive part of a cache lookup/insert, so it's
+/// deliberately kept out of `Cache`'s own methods (see [`Cache::get`] and
+/// [`build_entry`]) — callers do that I/O without holding the cache's lock,
+/// and only take the lock to read/write the `HashMap` itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    /// Cheap first/last-block hash, checked before anything else.
+    partial_hash: u128,
+    /// Hash of the whole file, only computed to confirm a partial-hash hit.
+    full_hash: u128,
+    /// Hash of the `valid_bb` set the entry was unified against.
+    valid_bb_hash: u128,
+    /// Unified file this input produced, relative to the output directory
+    /// the cache's sidecar file lives in (so the cache stays valid if the
+    /// whole output directory is moved, and isn't silently cwd-dependent).
+    output_file: PathBuf,
+}
+
+/// Persistent cache of input -> unified-output mappings, keyed by input path.
+///
+/// Stored as a sidecar JSON file inside the output directory (see
+/// [`Cache::sidecar_path`]) so repeated
+/// runs over a growing corpus only have to reprocess what actually changed.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cache {
+    entries: HashMap<PathBuf, CacheEntry>,
+}
+
+impl Cache {
+    /// Path of the sidecar cache file for a given output directory.
+    pub fn sidecar_path(output_path: &Path) -> PathBuf {
+        output_path.join(".tut-cache.json")
+    }
+
+    /// Load the cache from `path`, or an empty cache if it doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        let Ok(contents) = fs::read_to_string(path) else {
+            return Ok(Self::default());
+        };
+
+        serde_json::from_str(&contents).with_context(|| format!("Could not parse cache {:?}", path))
+    }
+
+    /// Write the cache back out to `path`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(path, contents).with_context(|| format!("Could not write cache {:?}", path))
+    }
+
+    /// Clone of the entry recorded for `input_path`, if any.
+    ///
+    /// Only touches the `HashMap`, so callers can release the cache's lock
+    /// before handing the entry to [`is_fresh`], which does the actual
+    /// hashing I/O.
+    pub fn get(&self, input_path: &Path) -> Option<CacheEntry> {
+        self.entries.get(input_path).cloned()
+    }
+
+    /// Record `entry` as the result of unifying `input_path`, replacing
+    /// whatever was recorded for it before.
+    ///
+    /// Only touches the `HashMap` — build `entry` with [`build_entry`]
+    /// first, outside the cache's lock.
+    pub fn insert(&mut self, input_path: PathBuf, entry: CacheEntry) {
+        self.entries.insert(input_path, entry);
+    }
+}
+
+/// Returns `true` if `entry` (previously recorded for `input_path`) is still
+/// up to date: it was unified against the same `valid_bb` set, its output
+/// file (resolved against `output_dir`) is still on disk, and `input_path`
+/// hashes the same as last time (partial hash, then full hash to rule out a
+/// collision).
+///
+/// Does the hashing itself, so call this without holding the cache's lock.
+pub fn is_fresh(
+    entry: &CacheEntry,
+    input_path: &Path,
+    output_dir: &Path,
+    valid_bb_hash: u128,
+) -> Result<bool> {
+    if entry.valid_bb_hash != valid_bb_hash || !output_dir.join(&entry.output_file).exists() {
+        return Ok(false);
+    }
+
+    if entry.partial_hash != partial_hash(input_path)? {
+        return Ok(false);
+    }
+
+    Ok(entry.full_hash == full_hash(input_path)?)
+}
+
+/// Build the [`CacheEntry`] for having just unified `input_path` into
+/// `output_file`, which must live under `output_dir` (the directory the
+/// cache's own sidecar file sits in).
+///
+/// Does the hashing itself, so call this without holding the cache's lock.
+pub fn build_entry(
+    input_path: &Path,
+    output_file: &Path,
+    output_dir: &Path,
+    valid_bb_hash: u128,
+) -> Result<CacheEntry> {
+    let output_file = output_file
+        .strip_prefix(output_dir)
+        .unwrap_or(output_file)
+        .to_path_buf();
+
+    Ok(CacheEntry {
+        partial_hash: partial_hash(input_path)?,
+        full_hash: full_hash(input_path)?,
+        valid_bb_hash,
+        output_file,
+    })
+}
+
+/// Hash of the full contents of `path`.
+fn full_hash(path: &Path) -> Result<u128> {
+    let mut file = File::open(path).with_context(|| format!("Could not read file {:?}", path))?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+
+    Ok(xxh3_128(&contents))
+}
+
+/// Cheap hash over just the first and last [`PARTIAL_HASH_BLOCK`] bytes of
+/// `path`, used as a fast pre-check before falling back to [`full_hash`].
+fn partial_hash(path: &Path) -> Result<u128> {
+    let mut file = File::open(path).with_context(|| format!("Could not read file {:?}", path))?;
+    let len = file.metadata()?.len();
+
+    let mut buf = Vec::with_capacity((PARTIAL_HASH_BLOCK * 2).min(len as usize));
+
+    let mut head = vec![0u8; PARTIAL_HASH_BLOCK.min(len as usize)];
+    file.read_exact(&mut head)?;
+    buf.extend_from_slice(&head);
+
+    let tail_len = PARTIAL_HASH_BLOCK.min(len as usize);
+    if len as usize > head.len() {
+        file.seek(SeekFrom::End(-(tail_len as i64)))?;
+        let mut tail = vec![0u8; tail_len];
+        file.read_exact(&mut tail)?;
+        buf.extend_from_slice(&tail);
+    }
+
+    Ok(xxh3_128(&buf))
+}
+
+/// Hash of the `valid_bb` set, so a cache entry is invalidated if the set of
+/// blocks considered valid changes between runs.
+pub fn valid_bb_hash(valid_bb: &[usize]) -> u128 {
+    let bytes: Vec<u8> = valid_bb.iter().flat_map(|bb| bb.to_le_bytes()).collect();
+
+    xxh3_128(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("tut-cache-test-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn partial_hash_changes_with_content() {
+        let path = temp_path("partial-hash-input");
+        fs::write(&path, b"hello world").unwrap();
+        let before = partial_hash(&path).unwrap();
+
+        fs::write(&path, b"hello there").unwrap();
+        let after = partial_hash(&path).unwrap();
+
+        assert_ne!(before, after);
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn is_fresh_tracks_content_bb_set_and_output_changes() {
+        let output_dir = temp_path("output-dir");
+        fs::create_dir_all(&output_dir).unwrap();
+        let input_path = temp_path("input");
+        let output_path = output_dir.join("trace.unified");
+
+        fs::write(&input_path, b"some trace content").unwrap();
+        fs::write(&output_path, b"unified output").unwrap();
+
+        let mut cache = Cache::default();
+        let entry = build_entry(&input_path, &output_path, &output_dir, 42).unwrap();
+        cache.insert(input_path.clone(), entry);
+        let entry = cache.get(&input_path).unwrap();
+
+        assert!(is_fresh(&entry, &input_path, &output_dir, 42).unwrap());
+        // Different `valid_bb` set invalidates the entry.
+        assert!(!is_fresh(&entry, &input_path, &output_dir, 43).unwrap());
+
+        // Changed input content invalidates the entry.
+        fs::write(&input_path, b"different trace content").unwrap();
+        assert!(!is_fresh(&entry, &input_path, &output_dir, 42).unwrap());
+        fs::write(&input_path, b"some trace content").unwrap();
+
+        // Missing output file invalidates the entry, even though the input
+        // is unchanged.
+        fs::remove_file(&output_path).unwrap();
+        assert!(!is_fresh(&entry, &input_path, &output_dir, 42).unwrap());
+
+        fs::remove_file(&input_path).ok();
+        fs::remove_dir_all(&output_dir).ok();
+    }
+}
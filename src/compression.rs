@@ -0,0 +1,174 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression as GzCompression};
+use xz2::{
+    read::XzDecoder,
+    stream::{Check, Filters, LzmaOptions, Stream},
+    write::XzEncoder,
+};
+use zstd::stream::{read::Decoder as ZstdDecoder, write::Encoder as ZstdEncoder};
+
+/// A trace-file writer that needs an explicit finalization step beyond a
+/// plain `Write`, so the codec footer (and any error while writing it) is
+/// never silently lost in a `Drop` impl.
+pub trait FinishWrite: Write {
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+impl FinishWrite for File {
+    fn finish(self: Box<Self>) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl FinishWrite for GzEncoder<File> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        GzEncoder::finish(*self).context("Failed to finish gzip stream")?;
+        Ok(())
+    }
+}
+
+impl FinishWrite for ZstdEncoder<'static, File> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        ZstdEncoder::finish(*self).context("Failed to finish zstd stream")?;
+        Ok(())
+    }
+}
+
+impl FinishWrite for XzEncoder<File> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        XzEncoder::finish(*self).context("Failed to finish xz stream")?;
+        Ok(())
+    }
+}
+
+/// 64 MiB dictionary for the xz encoder. BB traces have long-range
+/// repetition, so a window this much larger than the default preset's
+/// compresses them dramatically better.
+const XZ_DICT_SIZE: u32 = 64 * 1024 * 1024;
+
+/// Output compression codec for unified trace files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    /// Extension appended after `.unified` for this codec, or `None` for
+    /// uncompressed output.
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gz"),
+            Compression::Zstd => Some("zst"),
+            Compression::Xz => Some("xz"),
+        }
+    }
+
+    /// Wrap `file` in this codec's encoder, mapping `level` onto the
+    /// encoder's own preset range. Callers must call `finish()` on the
+    /// returned writer once done, to flush the codec footer and surface any
+    /// error instead of losing it on drop.
+    pub fn writer(self, file: File, level: u32) -> Result<Box<dyn FinishWrite>> {
+        let writer: Box<dyn FinishWrite> = match self {
+            Compression::None => Box::new(file),
+            Compression::Gzip => Box::new(GzEncoder::new(file, GzCompression::new(level.min(9)))),
+            Compression::Zstd => Box::new(ZstdEncoder::new(file, level as i32)?),
+            Compression::Xz => {
+                let mut options = LzmaOptions::new_preset(level.min(9))?;
+                options.dict_size(XZ_DICT_SIZE);
+
+                let mut filters = Filters::new();
+                filters.lzma2(&options);
+
+                let stream = Stream::new_stream_encoder(&filters, Check::Crc64)?;
+                Box::new(XzEncoder::new_stream(file, stream))
+            }
+        };
+
+        Ok(writer)
+    }
+}
+
+/// Open `path` for reading, transparently decompressing it if its leading
+/// bytes match a known magic number.
+///
+/// This is independent of `--compress`: a `.unified.zst` produced by an
+/// earlier run can be re-ingested regardless of what codec (if any) is
+/// requested for this run's output.
+pub fn reader(path: &std::path::Path) -> Result<Box<dyn BufRead>> {
+    let mut file = File::open(path)?;
+
+    // `Read::read` is allowed to return short reads even for a regular file,
+    // so fill the buffer in a loop rather than trusting a single call —
+    // otherwise a short first read on a real xz/zstd file would misclassify
+    // it as plain text. A genuinely short file (fewer than 6 bytes) just
+    // leaves the tail of `magic` zeroed, which matches no known signature.
+    let mut magic = [0u8; 6];
+    let mut read = 0;
+    loop {
+        match file.read(&mut magic[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    file.seek(SeekFrom::Start(0))?;
+
+    let reader: Box<dyn BufRead> = if read >= 2 && magic[..2] == [0x1f, 0x8b] {
+        Box::new(BufReader::new(GzDecoder::new(file)))
+    } else if read >= 4 && magic[..4] == [0x28, 0xb5, 0x2f, 0xfd] {
+        Box::new(BufReader::new(ZstdDecoder::new(file)?))
+    } else if read >= 6 && magic[..6] == [0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00] {
+        Box::new(BufReader::new(XzDecoder::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+
+    Ok(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("tut-compression-test-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn round_trips_every_codec() {
+        for (compression, level) in [
+            (Compression::None, 1),
+            (Compression::Gzip, 6),
+            (Compression::Zstd, 3),
+            (Compression::Xz, 1),
+        ] {
+            let path = temp_path(&format!("{compression:?}"));
+
+            let file = File::create(&path).unwrap();
+            let mut writer = compression.writer(file, level).unwrap();
+            writeln!(writer, "first line").unwrap();
+            writeln!(writer, "second line").unwrap();
+            writer.finish().unwrap();
+
+            let lines: Vec<String> = reader(&path)
+                .unwrap()
+                .lines()
+                .collect::<std::io::Result<_>>()
+                .unwrap();
+            assert_eq!(lines, vec!["first line", "second line"]);
+
+            fs::remove_file(&path).ok();
+        }
+    }
+}
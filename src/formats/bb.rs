@@ -0,0 +1,168 @@
+use std::{
+    fmt::{self, Display},
+    io::BufRead,
+};
+
+use anyhow::{Context, Result};
+
+use super::{Entry, ParseOutcome, TraceFormat};
+
+/// Parse one `<id> <program_counter> <hit_counter>` line.
+fn parse_line(line: &str) -> Result<(usize, usize, usize)> {
+    let parts: Vec<&str> = line.splitn(3, ' ').collect();
+    anyhow::ensure!(parts.len() == 3, "expected 3 space-separated fields, got {}", parts.len());
+
+    let id = usize::from_str_radix(parts[0], 16).context("invalid id")?;
+    let pc = usize::from_str_radix(parts[1], 16).context("invalid program counter")?;
+    let hit_count = parts[2].parse::<usize>().context("invalid hit counter")?;
+
+    Ok((id, pc, hit_count))
+}
+
+#[derive(Debug)]
+pub struct BasicBlockEntry {
+    pub id: usize,
+    pub program_counter: usize,
+    pub hit_counter: usize,
+}
+
+impl Display for BasicBlockEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04x} {:x} {}",
+            self.id, self.program_counter, self.hit_counter
+        )
+    }
+}
+
+/// Basic-block traces: one `<id> <program_counter> <hit_counter>` line per
+/// recorded block, all fields hex except the hit counter.
+pub struct BbFormat;
+
+impl TraceFormat for BbFormat {
+    fn parse(
+        &self,
+        path_for_errors: &str,
+        reader: Box<dyn BufRead>,
+        valid_bb: &[usize],
+        verbose: bool,
+        skip_broken: bool,
+    ) -> Result<ParseOutcome> {
+        let mut entries = Vec::new();
+        let mut ids = Vec::new();
+        let mut program_counters = Vec::new();
+        let mut hit_counters = Vec::new();
+
+        let mut id_offset = 0;
+        let mut lines_total = 0;
+        let mut skipped_lines = 0;
+
+        for (line_number, line) in reader.lines().enumerate() {
+            lines_total += 1;
+
+            let line = match line {
+                Ok(line) => line,
+                Err(e) if skip_broken => {
+                    skipped_lines += 1;
+                    eprintln!(
+                        "Skipping unreadable line {} in {:?}: {:#}",
+                        line_number + 1,
+                        path_for_errors,
+                        e
+                    );
+                    continue;
+                }
+                Err(e) => return Err(e).context(format!("Could not read {:?}", path_for_errors)),
+            };
+
+            let (id, pc, hit_count) = match parse_line(&line) {
+                Ok(parsed) => parsed,
+                Err(e) if skip_broken => {
+                    skipped_lines += 1;
+                    eprintln!(
+                        "Skipping malformed line {} in {:?}: {:#}",
+                        line_number + 1,
+                        path_for_errors,
+                        e
+                    );
+                    continue;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Malformed line {} in {:?}", line_number + 1, path_for_errors)
+                    })
+                }
+            };
+
+            if !valid_bb.contains(&pc) {
+                id_offset += 1;
+                continue;
+            }
+
+            let Some(shifted_id) = id.checked_sub(id_offset) else {
+                // The running offset has overtaken this entry's id, which
+                // means the ids in this file aren't monotonically
+                // increasing as assumed. Treat it the same as any other
+                // malformed line rather than panicking on the underflow.
+                if skip_broken {
+                    skipped_lines += 1;
+                    eprintln!(
+                        "Skipping line {} in {:?}: id {:#x} is behind the running offset {}",
+                        line_number + 1,
+                        path_for_errors,
+                        id,
+                        id_offset
+                    );
+                    id_offset += 1;
+                    continue;
+                }
+
+                anyhow::bail!(
+                    "Malformed line {} in {:?}: id {:#x} is behind the running offset {}",
+                    line_number + 1,
+                    path_for_errors,
+                    id,
+                    id_offset
+                );
+            };
+
+            ids.push(shifted_id);
+            program_counters.push(pc);
+            hit_counters.push(hit_count);
+        }
+
+        for i in 0..ids.len() {
+            entries.push(Entry::Bb(BasicBlockEntry {
+                id: ids[i],
+                program_counter: program_counters[i],
+                hit_counter: hit_counters[i],
+            }));
+        }
+
+        if verbose {
+            println!(
+                "{} basic block entries deleted for file: '{}'",
+                id_offset, path_for_errors
+            );
+        }
+
+        Ok(ParseOutcome {
+            entries,
+            lines_total,
+            skipped_lines,
+        })
+    }
+
+    fn detect(first_lines: &[String]) -> bool {
+        let Some(first_line) = first_lines.iter().find(|l| !l.is_empty()) else {
+            return false;
+        };
+
+        let parts: Vec<&str> = first_line.splitn(3, ' ').collect();
+        parts.len() == 3
+            && usize::from_str_radix(parts[0], 16).is_ok()
+            && usize::from_str_radix(parts[1], 16).is_ok()
+            && parts[2].parse::<usize>().is_ok()
+    }
+}
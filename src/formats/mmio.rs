@@ -0,0 +1,122 @@
+use std::{
+    fmt::{self, Display},
+    io::BufRead,
+};
+
+use anyhow::{Context, Result};
+
+use super::{Entry, ParseOutcome, TraceFormat};
+
+#[derive(Debug)]
+pub struct MmioEntry {
+    pub id: usize,
+    pub address: usize,
+    pub value: usize,
+    pub is_write: bool,
+}
+
+impl Display for MmioEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04x} {:x} {:x} {}",
+            self.id,
+            self.address,
+            self.value,
+            if self.is_write { "w" } else { "r" }
+        )
+    }
+}
+
+/// Parse one `MMIO <id> <address> <value> <r|w>` line.
+fn parse_line(line: &str) -> Result<MmioEntry> {
+    let parts: Vec<&str> = line.splitn(5, ' ').collect();
+    let [_tag, id, address, value, direction] = parts[..] else {
+        anyhow::bail!("expected 5 space-separated fields, got {}", parts.len());
+    };
+
+    let id = usize::from_str_radix(id, 16).context("invalid id")?;
+    let address = usize::from_str_radix(address, 16).context("invalid address")?;
+    let value = usize::from_str_radix(value, 16).context("invalid value")?;
+    let is_write = match direction {
+        "w" => true,
+        "r" => false,
+        other => anyhow::bail!("invalid direction {:?}", other),
+    };
+
+    Ok(MmioEntry {
+        id,
+        address,
+        value,
+        is_write,
+    })
+}
+
+/// MMIO traces: `MMIO <id> <address> <value> <r|w>` lines recording reads
+/// from and writes to memory-mapped registers.
+pub struct MmioFormat;
+
+impl TraceFormat for MmioFormat {
+    fn parse(
+        &self,
+        path_for_errors: &str,
+        reader: Box<dyn BufRead>,
+        _valid_bb: &[usize],
+        _verbose: bool,
+        skip_broken: bool,
+    ) -> Result<ParseOutcome> {
+        let mut entries = Vec::new();
+        let mut lines_total = 0;
+        let mut skipped_lines = 0;
+
+        for (line_number, line) in reader.lines().enumerate() {
+            lines_total += 1;
+
+            let line = match line {
+                Ok(line) => line,
+                Err(e) if skip_broken => {
+                    skipped_lines += 1;
+                    eprintln!(
+                        "Skipping unreadable line {} in {:?}: {:#}",
+                        line_number + 1,
+                        path_for_errors,
+                        e
+                    );
+                    continue;
+                }
+                Err(e) => return Err(e).context(format!("Could not read {:?}", path_for_errors)),
+            };
+
+            match parse_line(&line) {
+                Ok(entry) => entries.push(Entry::Mmio(entry)),
+                Err(e) if skip_broken => {
+                    skipped_lines += 1;
+                    eprintln!(
+                        "Skipping malformed line {} in {:?}: {:#}",
+                        line_number + 1,
+                        path_for_errors,
+                        e
+                    );
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Malformed line {} in {:?}", line_number + 1, path_for_errors)
+                    })
+                }
+            }
+        }
+
+        Ok(ParseOutcome {
+            entries,
+            lines_total,
+            skipped_lines,
+        })
+    }
+
+    fn detect(first_lines: &[String]) -> bool {
+        first_lines
+            .iter()
+            .find(|l| !l.is_empty())
+            .is_some_and(|l| l.starts_with("MMIO "))
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    fmt::{self, Display},
+    io::BufRead,
+};
+
+use anyhow::{Context, Result};
+
+use super::{Entry, ParseOutcome, TraceFormat};
+
+#[derive(Debug)]
+pub struct RamEntry {
+    pub id: usize,
+    pub address: usize,
+    pub value: usize,
+}
+
+impl Display for RamEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04x} {:x} {:x}", self.id, self.address, self.value)
+    }
+}
+
+/// Parse one `RAM <id> <address> <value>` line.
+fn parse_line(line: &str) -> Result<RamEntry> {
+    let parts: Vec<&str> = line.splitn(4, ' ').collect();
+    let [_tag, id, address, value] = parts[..] else {
+        anyhow::bail!("expected 4 space-separated fields, got {}", parts.len());
+    };
+
+    let id = usize::from_str_radix(id, 16).context("invalid id")?;
+    let address = usize::from_str_radix(address, 16).context("invalid address")?;
+    let value = usize::from_str_radix(value, 16).context("invalid value")?;
+
+    Ok(RamEntry { id, address, value })
+}
+
+/// RAM traces: `RAM <id> <address> <value>` lines recording writes into
+/// plain memory (as opposed to the memory-mapped registers MMIO covers).
+pub struct RamFormat;
+
+impl TraceFormat for RamFormat {
+    fn parse(
+        &self,
+        path_for_errors: &str,
+        reader: Box<dyn BufRead>,
+        _valid_bb: &[usize],
+        _verbose: bool,
+        skip_broken: bool,
+    ) -> Result<ParseOutcome> {
+        let mut entries = Vec::new();
+        let mut lines_total = 0;
+        let mut skipped_lines = 0;
+
+        for (line_number, line) in reader.lines().enumerate() {
+            lines_total += 1;
+
+            let line = match line {
+                Ok(line) => line,
+                Err(e) if skip_broken => {
+                    skipped_lines += 1;
+                    eprintln!(
+                        "Skipping unreadable line {} in {:?}: {:#}",
+                        line_number + 1,
+                        path_for_errors,
+                        e
+                    );
+                    continue;
+                }
+                Err(e) => return Err(e).context(format!("Could not read {:?}", path_for_errors)),
+            };
+
+            match parse_line(&line) {
+                Ok(entry) => entries.push(Entry::Ram(entry)),
+                Err(e) if skip_broken => {
+                    skipped_lines += 1;
+                    eprintln!(
+                        "Skipping malformed line {} in {:?}: {:#}",
+                        line_number + 1,
+                        path_for_errors,
+                        e
+                    );
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Malformed line {} in {:?}", line_number + 1, path_for_errors)
+                    })
+                }
+            }
+        }
+
+        Ok(ParseOutcome {
+            entries,
+            lines_total,
+            skipped_lines,
+        })
+    }
+
+    fn detect(first_lines: &[String]) -> bool {
+        first_lines
+            .iter()
+            .find(|l| !l.is_empty())
+            .is_some_and(|l| l.starts_with("RAM "))
+    }
+}
@@ -1,29 +1,30 @@
+mod aggregate;
+mod cache;
+mod compression;
+mod formats;
+mod report;
+
 use std::{
-    fmt::{self, Display},
+    fmt::Display,
     fs::{self, File},
     io::{BufRead, BufReader, Write},
+    num::NonZeroUsize,
     path::PathBuf,
+    sync::Mutex,
 };
 
 use anyhow::{Context, Result};
 use clap::Parser;
+use rayon::prelude::*;
 
-#[derive(Debug)]
-struct BasicBlockEntry {
-    id: usize,
-    program_counter: usize,
-    hit_counter: usize,
-}
+use aggregate::{Aggregator, AGGREGATE_FILE_STEM};
+use cache::Cache;
+use compression::Compression;
+use formats::{resolve_format, TraceFormatArg};
+use report::Report;
 
-impl Display for BasicBlockEntry {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{:04x} {:x} {}",
-            self.id, self.program_counter, self.hit_counter
-        )
-    }
-}
+/// Number of leading lines read from a trace file to sniff its format.
+const FORMAT_SNIFF_LINES: usize = 5;
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -38,74 +39,62 @@ struct Cli {
     /// Flag to enable verbose output
     #[clap(long, short)]
     verbose: bool,
+    /// Number of threads to use for unifying trace files in parallel
+    /// (defaults to the number of logical cores)
+    #[clap(long, short = 'j')]
+    threads: Option<NonZeroUsize>,
+    /// Trace format to parse input files as; `auto` sniffs each file individually
+    #[clap(long, value_enum, default_value = "auto")]
+    format: TraceFormatArg,
+    /// Compression codec applied to unified output files
+    #[clap(long, value_enum, default_value = "none")]
+    compress: Compression,
+    /// Compression level passed to the chosen codec's own preset range
+    #[clap(long, default_value_t = 6)]
+    compression_level: u32,
+    /// Skip the incremental cache and reprocess every trace file
+    #[clap(long)]
+    no_cache: bool,
+    /// Tolerate malformed lines/files instead of aborting the whole run:
+    /// bad lines are skipped and fully-unparseable files are quarantined
+    /// into a `broken/` subdirectory, with a summary printed at the end
+    #[clap(long)]
+    skip_broken: bool,
+    /// After unifying, also fold every trace's basic-block entries into one
+    /// combined `pc total_hits files_covering` coverage profile
+    #[clap(long)]
+    aggregate: bool,
 }
 
-fn parse_bb_trace_file(
-    path: &PathBuf,
-    valid_bb: &[usize],
-    verbose: bool,
-) -> Result<Vec<BasicBlockEntry>> {
-    let trace_file = File::open(path).with_context(|| format!("Could not read file {:?}", path))?;
-
-    let mut entries = Vec::new();
-
-    let reader = BufReader::new(trace_file);
-    let mut ids = Vec::new();
-    let mut program_counters = Vec::new();
-    let mut hit_counters = Vec::new();
-
-    let mut id_offset = 0;
-
-    for line in reader.lines().map(|l| l.unwrap()) {
-        let parts: Vec<&str> = line.splitn(3, ' ').collect();
-        let id = usize::from_str_radix(parts[0], 16)?;
-        let pc = usize::from_str_radix(parts[1], 16)?;
-        let hit_count = parts[2].parse::<usize>()?;
-
-        if valid_bb.contains(&pc) {
-            ids.push(id - id_offset);
-            program_counters.push(pc);
-            hit_counters.push(hit_count);
-        } else {
-            id_offset += 1;
-        }
-    }
-
-    // Truncate IDs
-    ids.truncate(program_counters.len());
-
-    // Ensure integrity
-    assert_eq!(ids.len(), program_counters.len());
-    assert_eq!(program_counters.len(), hit_counters.len());
-
-    for i in 0..ids.len() {
-        entries.push(BasicBlockEntry {
-            id: ids[i],
-            program_counter: program_counters[i],
-            hit_counter: hit_counters[i],
-        });
-    }
-
-    if verbose {
-        println!(
-            "{} basic block entries deleted for file: '{}'",
-            id_offset,
-            path.to_string_lossy()
-        );
-    }
-
-    Ok(entries)
+/// Read the first [`FORMAT_SNIFF_LINES`] lines of `path`, for format sniffing.
+/// Transparently decompresses the file first, so a previously unified
+/// `.unified.zst` (or `.gz`/`.xz`) can be sniffed just like a plain file.
+fn peek_lines(path: &PathBuf) -> Result<Vec<String>> {
+    compression::reader(path)
+        .with_context(|| format!("Could not read file {:?}", path))?
+        .lines()
+        .take(FORMAT_SNIFF_LINES)
+        .collect::<std::io::Result<_>>()
+        .with_context(|| format!("Could not read file {:?}", path))
 }
 
-fn write_trace_file<T: Display>(traces: &[T], file_path: PathBuf) -> Result<()> {
-    let mut unified_trace_file = File::create(&file_path)
+fn write_trace_file<T: Display>(
+    traces: &[T],
+    file_path: PathBuf,
+    compression: Compression,
+    compression_level: u32,
+) -> Result<()> {
+    let unified_trace_file = File::create(&file_path)
         .with_context(|| format!("Unable to create output file {:?}", &file_path))?;
+    let mut writer = compression.writer(unified_trace_file, compression_level)?;
 
     for trace in traces.iter() {
-        writeln!(unified_trace_file, "{}", trace)?;
+        writeln!(writer, "{}", trace)?;
     }
 
-    Ok(())
+    writer
+        .finish()
+        .with_context(|| format!("Failed to finalize output file {:?}", &file_path))
 }
 
 fn main() -> Result<()> {
@@ -128,35 +117,180 @@ fn main() -> Result<()> {
         PathBuf::new()
     };
 
-    // TODO: Auto-detect trace format ((mmio?), bb, (ram?))
-    // TODO: Parallelize
-    // Handle all trace files
+    // Flatten trace files/directories into a single list of paths upfront so the
+    // actual unification work below can be spread across the thread pool.
+    let mut trace_paths = Vec::new();
     for trace_file in args.trace_files {
-        let trace_paths = if let Ok(dir_entries) = fs::read_dir(&trace_file) {
-            dir_entries
-                .into_iter()
-                .filter_map(|d| d.ok())
-                .map(|e| e.path())
-                .collect::<Vec<PathBuf>>()
+        if let Ok(dir_entries) = fs::read_dir(&trace_file) {
+            trace_paths.extend(
+                dir_entries
+                    .into_iter()
+                    .filter_map(|d| d.ok())
+                    .map(|e| e.path()),
+            );
         } else {
             // Either error happened or the trace file isn't a directory,
             // will handle error case later
-            vec![trace_file]
-        };
+            trace_paths.push(trace_file);
+        }
+    }
+
+    let threads = args
+        .threads
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map(NonZeroUsize::get)
+        .unwrap_or(1);
 
-        for path in trace_paths {
-            // Only read valid traces from valid BBs (unification)
-            let traces = parse_bb_trace_file(&path, &valid_bb, args.verbose)
-                .with_context(|| format!("Error while parsing trace file {:?}", &path))?;
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .context("Unable to build thread pool")?;
 
-            // Write back unified traces
-            let mut unified_trace_file_path = output_path.clone();
+    // `valid_bb` is immutable for the rest of the run, so every worker can
+    // simply borrow it.
+    let valid_bb = &valid_bb;
+    let valid_bb_hash = cache::valid_bb_hash(valid_bb);
 
-            unified_trace_file_path.push(&path.file_name().unwrap());
-            unified_trace_file_path.set_extension("unified");
+    // `--aggregate` needs every file's freshly parsed entries to fold into the
+    // combined profile, so it always bypasses the incremental cache.
+    let skip_cache = args.no_cache || args.aggregate;
 
-            write_trace_file(&traces, unified_trace_file_path)?;
+    let cache_path = Cache::sidecar_path(&output_path);
+    let cache = if skip_cache {
+        Cache::default()
+    } else {
+        Cache::load(&cache_path)?
+    };
+    let cache = Mutex::new(cache);
+    let report = Report::default();
+    let aggregator = Aggregator::default();
+
+    pool.install(|| {
+        trace_paths.par_iter().try_for_each(|path| {
+            // Only the `HashMap` lookup itself happens under the lock;
+            // `cache::is_fresh` does its hashing I/O afterwards so workers
+            // don't serialize on each other's file reads.
+            if !skip_cache {
+                let entry = cache.lock().unwrap().get(path);
+                if let Some(entry) = entry {
+                    if cache::is_fresh(&entry, path, &output_path, valid_bb_hash)? {
+                        if args.verbose {
+                            println!(
+                                "Skipping up-to-date trace file: '{}'",
+                                path.to_string_lossy()
+                            );
+                        }
+
+                        return Ok(());
+                    }
+                }
+            }
+
+            let result = (|| -> Result<()> {
+                let first_lines = peek_lines(path)
+                    .with_context(|| format!("Error while sniffing trace file {:?}", path))?;
+                let format = resolve_format(args.format, &first_lines)
+                    .with_context(|| format!("Error while detecting format of {:?}", path))?;
+
+                let reader = compression::reader(path)
+                    .with_context(|| format!("Could not read file {:?}", path))?;
+
+                // Only read valid traces from valid BBs (unification)
+                let outcome = format
+                    .parse(
+                        &path.to_string_lossy(),
+                        reader,
+                        valid_bb,
+                        args.verbose,
+                        args.skip_broken,
+                    )
+                    .with_context(|| format!("Error while parsing trace file {:?}", path))?;
+
+                if args.skip_broken {
+                    report.record_skipped_lines(outcome.skipped_lines);
+
+                    // A legitimate trace can still yield zero entries (every
+                    // block it recorded was outside `valid_bb`); only treat
+                    // the file as broken if every line actually failed to
+                    // parse.
+                    if outcome.lines_total > 0 && outcome.skipped_lines == outcome.lines_total {
+                        anyhow::bail!("every line in the file was unparseable");
+                    }
+                }
+
+                if args.aggregate {
+                    aggregator.record(&outcome.entries);
+                }
+
+                // Write back unified traces
+                let mut unified_trace_file_path = output_path.clone();
+
+                let mut unified_file_name = path.file_name().unwrap().to_os_string();
+                unified_file_name.push(".unified");
+                if let Some(extension) = args.compress.extension() {
+                    unified_file_name.push(".");
+                    unified_file_name.push(extension);
+                }
+                unified_trace_file_path.push(unified_file_name);
+
+                write_trace_file(
+                    &outcome.entries,
+                    unified_trace_file_path.clone(),
+                    args.compress,
+                    args.compression_level,
+                )?;
+
+                // Hash outside the lock — only the `HashMap` insert itself
+                // needs it held.
+                let entry =
+                    cache::build_entry(path, &unified_trace_file_path, &output_path, valid_bb_hash)?;
+                cache.lock().unwrap().insert(path.clone(), entry);
+
+                Ok(())
+            })();
+
+            match result {
+                Ok(()) => Ok(()),
+                Err(e) if args.skip_broken => {
+                    report::quarantine(path, &output_path)
+                        .with_context(|| format!("Error while quarantining {:?}", path))?;
+                    report.record_broken_file(path.clone());
+
+                    if args.verbose {
+                        println!("Quarantined '{}': {:#}", path.to_string_lossy(), e);
+                    }
+
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            }
+        })
+    })?;
+
+    // `skip_cache` means the in-memory cache started empty (and, for plain
+    // `--no-cache`, may only cover a subset of the corpus this run touched);
+    // saving it would clobber the real cache on disk with that partial view.
+    if !skip_cache {
+        cache.into_inner().unwrap().save(&cache_path)?;
+    }
+
+    if args.skip_broken {
+        report.print_summary();
+    }
+
+    if args.aggregate {
+        let rows = aggregator.into_rows();
+
+        let mut aggregate_path = output_path.clone();
+        let mut aggregate_file_name = std::ffi::OsString::from(AGGREGATE_FILE_STEM);
+        aggregate_file_name.push(".unified");
+        if let Some(extension) = args.compress.extension() {
+            aggregate_file_name.push(".");
+            aggregate_file_name.push(extension);
         }
+        aggregate_path.push(aggregate_file_name);
+
+        write_trace_file(&rows, aggregate_path, args.compress, args.compression_level)?;
     }
 
     Ok(())
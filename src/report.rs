@@ -0,0 +1,59 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+
+/// Subdirectory (under the output path) that quarantined files are moved
+/// into when running with `--skip-broken`.
+const BROKEN_DIR: &str = "broken";
+
+/// Summary of a lenient (`--skip-broken`) run: how many individual lines
+/// were skipped, and which files were rejected outright.
+#[derive(Debug, Default)]
+pub struct Report {
+    skipped_lines: Mutex<usize>,
+    broken_files: Mutex<Vec<PathBuf>>,
+}
+
+impl Report {
+    pub fn record_skipped_lines(&self, count: usize) {
+        *self.skipped_lines.lock().unwrap() += count;
+    }
+
+    pub fn record_broken_file(&self, path: PathBuf) {
+        self.broken_files.lock().unwrap().push(path);
+    }
+
+    /// Print the counts collected over the run; a no-op if nothing was
+    /// skipped or rejected.
+    pub fn print_summary(&self) {
+        let skipped_lines = *self.skipped_lines.lock().unwrap();
+        let broken_files = self.broken_files.lock().unwrap();
+
+        if skipped_lines == 0 && broken_files.is_empty() {
+            return;
+        }
+
+        println!("--- skip-broken summary ---");
+        println!("{} line(s) skipped", skipped_lines);
+        println!("{} file(s) fully rejected:", broken_files.len());
+        for path in broken_files.iter() {
+            println!("  {}", path.to_string_lossy());
+        }
+    }
+}
+
+/// Move `path` into a `broken/` subdirectory of `output_path`, leaving the
+/// good traces alone instead of failing the whole run over one bad file.
+pub fn quarantine(path: &Path, output_path: &Path) -> Result<()> {
+    let broken_dir = output_path.join(BROKEN_DIR);
+    fs::create_dir_all(&broken_dir)
+        .with_context(|| format!("Unable to create quarantine dir {:?}", broken_dir))?;
+
+    let destination = broken_dir.join(path.file_name().unwrap());
+    fs::rename(path, &destination)
+        .with_context(|| format!("Unable to move {:?} to {:?}", path, destination))
+}